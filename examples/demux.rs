@@ -21,7 +21,7 @@ async fn main() {
     ]);
 
     let (mut i32_stream, mut f64_stream, mut str_stream) =
-        demux!(MyEnum { A, B, C })(stream, error_handler::panicking());
+        demux!(MyEnum { A, B, C })(stream, error_handler::legacy(error_handler::panicking()));
 
     assert_eq!(i32_stream.next().await, Some(123));
     assert_eq!(i32_stream.next().await, Some(811));
@@ -0,0 +1,59 @@
+use mux_stream::{demux_batched, error_handler};
+
+use std::time::Duration;
+
+use futures::stream;
+
+#[derive(Debug)]
+enum MyEnum {
+    A(i32),
+    B(u8),
+}
+
+#[tokio::main]
+async fn main() {
+    // Size-triggered flush: 3 A's with max_size=2 flush [1, 2] immediately,
+    // then flush the remainder [3] on stream termination.
+    let input = stream::iter(vec![MyEnum::A(1), MyEnum::A(2), MyEnum::A(3), MyEnum::B(9)]);
+
+    let (mut a_rx, mut b_rx) = demux_batched!(
+        MyEnum { A, B },
+        2,
+        Duration::from_secs(10),
+        input,
+        error_handler::panicking::<MyEnum>(),
+    );
+
+    assert_eq!(a_rx.recv().await, Some(vec![1, 2]));
+    assert_eq!(a_rx.recv().await, Some(vec![3]));
+    assert_eq!(a_rx.recv().await, None);
+
+    assert_eq!(b_rx.recv().await, Some(vec![9]));
+    assert_eq!(b_rx.recv().await, None);
+
+    // Passing a CancellationToken returns the task's JoinHandle alongside
+    // the output streams; cancelling mid-stream flushes any non-empty
+    // remainder before the task returns.
+    let token = tokio_util::sync::CancellationToken::new();
+    let (tx, never_ending_rx) = tokio::sync::mpsc::unbounded_channel::<MyEnum>();
+    let never_ending = tokio_stream::wrappers::UnboundedReceiverStream::new(never_ending_rx);
+
+    let ((mut a_rx, mut b_rx), handle) = demux_batched!(
+        MyEnum { A, B },
+        100,
+        Duration::from_secs(10),
+        never_ending,
+        error_handler::panicking::<MyEnum>(),
+        token.clone(),
+    );
+
+    tx.send(MyEnum::A(1)).unwrap();
+    tx.send(MyEnum::A(2)).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    token.cancel();
+
+    assert_eq!(a_rx.recv().await, Some(vec![1, 2]));
+    handle.await.unwrap();
+    assert!(a_rx.recv().await.is_none());
+    assert!(b_rx.recv().await.is_none());
+}
@@ -0,0 +1,47 @@
+use mux_stream::{demux_with_capacity, error_handler};
+
+use futures::stream;
+
+#[derive(Debug)]
+enum MyEnum {
+    A(i32),
+    B(f64),
+}
+
+#[tokio::main]
+async fn main() {
+    let input = stream::iter(vec![MyEnum::A(123), MyEnum::B(24.241), MyEnum::A(811)]);
+
+    let (mut i32_stream, mut f64_stream) =
+        demux_with_capacity!(MyEnum { A, B }, 16, input, error_handler::panicking());
+
+    assert_eq!(i32_stream.recv().await, Some(123));
+    assert_eq!(i32_stream.recv().await, Some(811));
+    assert_eq!(i32_stream.recv().await, None);
+
+    assert_eq!(f64_stream.recv().await, Some(24.241));
+    assert_eq!(f64_stream.recv().await, None);
+
+    // Passing a CancellationToken returns the task's JoinHandle alongside
+    // the output streams, so cancelling mid-stream can be awaited for a
+    // clean shutdown.
+    let token = tokio_util::sync::CancellationToken::new();
+    let (tx, never_ending_rx) = tokio::sync::mpsc::unbounded_channel::<MyEnum>();
+    let never_ending = tokio_stream::wrappers::UnboundedReceiverStream::new(never_ending_rx);
+
+    let ((mut i32_stream, mut f64_stream), handle) = demux_with_capacity!(
+        MyEnum { A, B },
+        4,
+        never_ending,
+        error_handler::ignoring(),
+        token.clone(),
+    );
+
+    tx.send(MyEnum::A(1)).unwrap();
+    assert_eq!(i32_stream.recv().await, Some(1));
+
+    token.cancel();
+    handle.await.unwrap();
+    assert!(i32_stream.recv().await.is_none());
+    assert!(f64_stream.recv().await.is_none());
+}
@@ -22,7 +22,7 @@ async fn main() {
         stream::iter(i32_values.clone()),
         stream::iter(u8_values.clone()),
         stream::iter(str_values.clone()),
-        error_handler::panicking(),
+        error_handler::legacy(error_handler::panicking()),
     );
 
     let (i32_results, u8_results, str_results) = result
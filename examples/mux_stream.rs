@@ -0,0 +1,24 @@
+use mux_stream::mux_stream;
+
+use futures::{stream, StreamExt};
+
+#[derive(Debug, PartialEq)]
+enum MyEnum {
+    A(i32),
+    B(u8),
+}
+
+#[tokio::main]
+async fn main() {
+    let combined = mux_stream!(MyEnum {
+        A = stream::iter(vec![123, 811]),
+        B = stream::iter(vec![88]),
+    });
+
+    let mut values: Vec<_> = combined.collect().await;
+    values.sort_by_key(|update| match update {
+        MyEnum::A(x) => *x,
+        MyEnum::B(x) => *x as i32,
+    });
+    assert_eq!(values, vec![MyEnum::B(88), MyEnum::A(123), MyEnum::A(811)]);
+}
@@ -0,0 +1,50 @@
+use mux_stream::{error_handler, mux_with_capacity};
+
+use tokio::sync::mpsc::Receiver;
+
+#[derive(Debug, Clone, PartialEq)]
+enum MyEnum {
+    A(i32),
+    B(u8),
+}
+
+#[tokio::main]
+async fn main() {
+    let mut rx: Receiver<MyEnum> = mux_with_capacity!(
+        MyEnum {
+            A = futures::stream::iter(vec![123, 811]),
+            B = futures::stream::iter(vec![88]),
+        },
+        16,
+        error_handler::panicking(),
+    );
+
+    let mut values = Vec::new();
+    while let Some(update) = rx.recv().await {
+        values.push(update);
+    }
+    assert_eq!(values.len(), 3);
+
+    // Passing a CancellationToken returns the task's JoinHandle alongside
+    // the receiver, so cancelling mid-stream can be awaited for a clean
+    // shutdown.
+    let token = tokio_util::sync::CancellationToken::new();
+    let (tx, never_ending_rx) = tokio::sync::mpsc::unbounded_channel::<i32>();
+    let never_ending = tokio_stream::wrappers::UnboundedReceiverStream::new(never_ending_rx);
+
+    let (mut rx, handle) = mux_with_capacity!(
+        MyEnum {
+            A = never_ending,
+        },
+        4,
+        error_handler::ignoring(),
+        token.clone(),
+    );
+
+    tx.send(1).unwrap();
+    assert_eq!(rx.recv().await, Some(MyEnum::A(1)));
+
+    token.cancel();
+    handle.await.unwrap();
+    assert!(rx.recv().await.is_none());
+}
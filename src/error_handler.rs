@@ -1,11 +1,58 @@
 //! Common error handlers for (de)multiplexing.
 
+use std::time::Duration;
+
 use futures::future::BoxFuture;
 use tokio::sync::mpsc::error::SendError;
 
+/// Tells a (de)multiplexer how to proceed after an error handler has been
+/// invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// Re-attempt the delivery of the update that failed to send.
+    Retry,
+    /// Discard the update that failed to send and carry on with the rest of
+    /// the stream.
+    Drop,
+    /// Stop the forwarding task and close the remaining output channels.
+    Abort,
+}
+
 pub type ErrorHandler<T> =
+    Box<dyn Fn(SendError<T>) -> BoxFuture<'static, ControlFlow> + Send + Sync + 'static>;
+
+/// The error handler type expected by [`mux!`](crate::mux) and
+/// [`demux!`](crate::demux), which forward to the external
+/// `mux_stream_macros` crate and so predate [`ControlFlow`]-based steering.
+pub type LegacyErrorHandler<T> =
     Box<dyn Fn(SendError<T>) -> BoxFuture<'static, ()> + Send + Sync + 'static>;
 
+/// Adapts an [`ErrorHandler<T>`] to the [`LegacyErrorHandler<T>`] contract
+/// expected by [`mux!`](crate::mux)/[`demux!`](crate::demux), e.g.
+/// `error_handler::legacy(error_handler::panicking())`.
+///
+/// [`ControlFlow::Retry`] and [`ControlFlow::Drop`] are both treated as
+/// "drop the update and carry on", since the legacy forwarding loop has no
+/// retry path; [`ControlFlow::Abort`] still panics, since the legacy loop
+/// has no way to stop early either. This means wrapping
+/// [`retrying`](crate::error_handler::retrying) here loses its backoff: use
+/// [`mux_with_capacity!`](crate::mux_with_capacity)/
+/// [`demux_with_capacity!`](crate::demux_with_capacity) instead if retrying
+/// matters.
+pub fn legacy<T>(handler: ErrorHandler<T>) -> LegacyErrorHandler<T>
+where
+    T: Send + 'static,
+{
+    Box::new(move |error| {
+        let verdict = handler(error);
+        Box::pin(async move {
+            if verdict.await == ControlFlow::Abort {
+                panic!("error_handler::legacy: ControlFlow::Abort is not supported by mux!/demux!, which cannot stop their forwarding task early");
+            }
+        })
+    })
+}
+
 /// A panicking error handler.
 pub fn panicking<T>() -> ErrorHandler<T>
 where
@@ -14,15 +61,16 @@ where
     Box::new(|error| Box::pin(async move { panic!(error) }))
 }
 
-/// An error handler that ignores an error.
+/// An error handler that ignores an error and drops the corresponding
+/// update.
 pub fn ignoring<T>() -> ErrorHandler<T>
 where
     T: Send + 'static,
 {
-    Box::new(|_error| Box::pin(async move {}))
+    Box::new(|_error| Box::pin(async move { ControlFlow::Drop }))
 }
 
-/// A logging error handler.
+/// A logging error handler that drops the corresponding update.
 #[cfg(feature = "logging")]
 pub fn logging<T>() -> ErrorHandler<T>
 where
@@ -31,6 +79,53 @@ where
     Box::new(|error| {
         Box::pin(async move {
             log::error!("{}", error);
+            ControlFlow::Drop
+        })
+    })
+}
+
+/// An error handler that retries delivery with exponential backoff before
+/// escalating to [`ControlFlow::Abort`].
+///
+/// Sleeps via [`tokio::time::sleep`], starting at `base_delay` and doubling
+/// after each failed attempt, for up to `max_attempts` retries. Once
+/// `max_attempts` is exhausted for a given update, returns
+/// [`ControlFlow::Abort`], which stops the forwarding task.
+///
+/// A single `ErrorHandler<T>` is invoked concurrently across all of a
+/// (de)multiplexer's input streams, so the attempt counter is keyed by
+/// [`std::mem::discriminant`] of the failing update rather than shared: a
+/// failure on one enum variant does not drain the retry budget of an
+/// unrelated, concurrently-failing variant. Construct a fresh handler per
+/// [`mux!`](crate::mux)/[`demux!`](crate::demux) call if separate calls
+/// should not share retry state either.
+pub fn retrying<T>(base_delay: Duration, max_attempts: u32) -> ErrorHandler<T>
+where
+    T: Send + 'static,
+{
+    use std::collections::HashMap;
+    use std::mem::Discriminant;
+    use std::sync::Mutex;
+
+    let attempts: Mutex<HashMap<Discriminant<T>, u32>> = Mutex::new(HashMap::new());
+
+    Box::new(move |error| {
+        let key = std::mem::discriminant(&error.0);
+        let attempt_no = {
+            let mut attempts = attempts.lock().unwrap();
+            let counter = attempts.entry(key).or_insert(0);
+            let attempt_no = *counter;
+            *counter += 1;
+            attempt_no
+        };
+
+        Box::pin(async move {
+            if attempt_no >= max_attempts {
+                return ControlFlow::Abort;
+            }
+
+            tokio::time::sleep(base_delay * 2u32.saturating_pow(attempt_no)).await;
+            ControlFlow::Retry
         })
     })
 }
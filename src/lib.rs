@@ -9,6 +9,14 @@ pub mod error_handler;
 #[doc(hidden)]
 pub use mux_stream_macros as macros;
 
+#[doc(hidden)]
+pub async fn wait_cancelled(token: &Option<tokio_util::sync::CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Multiplexes several streams into one.
 ///
 /// Accepts a list of variants in the form `MyEnumPath {VariantName0, ...,
@@ -31,6 +39,15 @@ pub use mux_stream_macros as macros;
 /// fails to propagate an update from one of input streams to an output stream.
 /// See also [our default error handlers].
 ///
+/// This macro forwards as-is to the external [`mux_stream_macros`] crate, so
+/// it predates [`ControlFlow`]-based steering and has neither
+/// [`CancellationToken`] nor [`JoinHandle`] support: its error handler must
+/// still return a bare `BoxFuture<'static, ()>`, not [`ErrorHandler<T>`]'s
+/// `BoxFuture<'static, ControlFlow>`. Wrap one of our handler constructors
+/// with [`error_handler::legacy`] to use it here, e.g.
+/// `error_handler::legacy(error_handler::panicking())`. For `ControlFlow`
+/// steering and cancellation support, use [`mux_with_capacity!`] instead.
+///
 /// It propagates updates into the result stream in any order, simultaneously
 /// from all the provided input streams (in a separate [Tokio task]). Updates
 /// into the output stream are being redirected as long as at least one input
@@ -62,7 +79,7 @@ pub use mux_stream_macros as macros;
 ///     stream::iter(i32_values.clone()),
 ///     stream::iter(u8_values.clone()),
 ///     stream::iter(str_values.clone()),
-///     error_handler::panicking(),
+///     error_handler::legacy(error_handler::panicking()),
 /// );
 ///
 /// let (i32_results, u8_results, str_results) = result
@@ -91,6 +108,12 @@ pub use mux_stream_macros as macros;
 ///
 /// [Tokio task]: tokio::task
 /// [our default error handlers]: crate::error_handler
+/// [`ControlFlow`]: crate::error_handler::ControlFlow
+/// [`ErrorHandler<T>`]: crate::error_handler::ErrorHandler
+/// [`error_handler::legacy`]: crate::error_handler::legacy
+/// [`CancellationToken`]: tokio_util::sync::CancellationToken
+/// [`JoinHandle`]: tokio::task::JoinHandle
+/// [`mux_with_capacity!`]: crate::mux_with_capacity
 #[macro_export]
 macro_rules! mux {
     ($enum_ty:path { $($variant:ident),+ $(,)? }) => {
@@ -98,6 +121,173 @@ macro_rules! mux {
     };
 }
 
+/// Multiplexes several streams into one, backed by a bounded channel.
+///
+/// Just like [`mux!`], but the underlying channel is bounded
+/// ([`tokio::sync::mpsc::channel`]) rather than unbounded, so a fast
+/// producer cannot outpace a slow consumer without bound. Because
+/// [`Sender::send`](tokio::sync::mpsc::Sender::send) is `async` and resolves
+/// only once the channel has spare capacity, the forwarding task naturally
+/// blocks until the output stream is drained, propagating backpressure all
+/// the way back to the input streams.
+///
+/// Unlike [`mux!`], which delegates to [`mux_stream_macros`] for its
+/// multi-stage, arbitrary-arity dispatch, this macro is implemented entirely
+/// with `macro_rules!` and so takes every argument in a single invocation,
+/// with each variant paired explicitly with the stream that feeds it.
+///
+/// ```ignore
+/// mux_with_capacity!(
+///     MyEnum {
+///         A = stream_a,
+///         B = stream_b,
+///     },
+///     capacity,
+///     error_handler,
+/// )
+/// ```
+///
+/// This returns a [`tokio::sync::mpsc::Receiver<MyEnum>`]. All the input
+/// streams are driven concurrently from a single [Tokio task] via
+/// `tokio::select!`; that task keeps running as long as at least one input
+/// stream is still active. `error_handler` is invoked when the multiplexer
+/// fails to propagate an update from one of the input streams, and its
+/// [`ControlFlow`] answer decides whether that update is retried, dropped,
+/// or the whole task is aborted. See [our default error handlers].
+///
+/// `MyEnum` must implement `Clone`: a failed send hands the update to
+/// `error_handler` while keeping a clone around, so it can be replayed if
+/// the answer is [`ControlFlow::Retry`]. This is required even if
+/// `error_handler` never returns `Retry`.
+///
+/// Optionally, a [`tokio_util::sync::CancellationToken`] may be passed as
+/// the last argument, after the error handler. When cancelled (or once
+/// every input stream has been exhausted, whichever happens first), the
+/// task stops pulling from the input streams, drops the output sender, and
+/// returns, letting the result stream terminate cleanly; pass one in and
+/// the macro also returns the task's [`tokio::task::JoinHandle`] alongside
+/// the result stream, so callers can `.await` it to confirm a clean
+/// shutdown. A pending retry is also raced against the token, so
+/// cancellation is not stuck behind an error handler's backoff.
+///
+/// ```
+/// use mux_stream::{error_handler, mux_with_capacity};
+///
+/// use futures::stream;
+/// use tokio::sync::mpsc::Receiver;
+///
+/// #[derive(Debug, Clone)]
+/// enum MyEnum {
+///     A(i32),
+///     B(u8),
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main_() {
+/// let mut rx: Receiver<MyEnum> = mux_with_capacity!(
+///     MyEnum {
+///         A = stream::iter(vec![123, 811]),
+///         B = stream::iter(vec![88]),
+///     },
+///     16,
+///     error_handler::panicking(),
+/// );
+///
+/// let mut values = Vec::new();
+/// while let Some(update) = rx.recv().await {
+///     values.push(update);
+/// }
+/// assert_eq!(values.len(), 3);
+/// # }
+/// ```
+///
+/// [`mux!`]: crate::mux
+/// [Tokio task]: tokio::task
+/// [our default error handlers]: crate::error_handler
+/// [`ControlFlow`]: crate::error_handler::ControlFlow
+#[macro_export]
+macro_rules! mux_with_capacity {
+    ($enum_ty:path { $($variant:ident = $stream:expr),+ $(,)? }, $capacity:expr, $error_handler:expr $(,)?) => {{
+        let (rx, _handle) = $crate::mux_with_capacity!(
+            @impl $enum_ty { $($variant = $stream),+ } $capacity, $error_handler, None
+        );
+        rx
+    }};
+    ($enum_ty:path { $($variant:ident = $stream:expr),+ $(,)? }, $capacity:expr, $error_handler:expr, $token:expr $(,)?) => {
+        $crate::mux_with_capacity!(@impl $enum_ty { $($variant = $stream),+ } $capacity, $error_handler, Some($token))
+    };
+    (@impl $enum_ty:path { $($variant:ident = $stream:expr),+ } $capacity:expr, $error_handler:expr, $token:expr) => {{
+        use futures::StreamExt as _;
+
+        let capacity: usize = $capacity;
+        let (tx, rx) = tokio::sync::mpsc::channel::<$enum_ty>(capacity);
+        let error_handler = $error_handler;
+        let token: Option<tokio_util::sync::CancellationToken> = $token;
+
+        paste::paste! {
+            $(let mut [<stream_ $variant:snake>] = $stream;)+
+            $(let mut [<active_ $variant:snake>] = true;)+
+
+            let handle = tokio::spawn(async move {
+                loop {
+                    if !($([<active_ $variant:snake>])||+) {
+                        break;
+                    }
+
+                    tokio::select! {
+                        $(
+                            item = [<stream_ $variant:snake>].next(), if [<active_ $variant:snake>] => {
+                                match item {
+                                    Some(payload) => {
+                                        let mut update = <$enum_ty>::$variant(payload);
+
+                                        loop {
+                                            let sent = tokio::select! {
+                                                result = tx.send(update) => Some(result),
+                                                _ = $crate::wait_cancelled(&token), if token.is_some() => None,
+                                            };
+
+                                            let result = match sent {
+                                                Some(result) => result,
+                                                None => return,
+                                            };
+
+                                            update = match result {
+                                                Ok(()) => break,
+                                                Err(tokio::sync::mpsc::error::SendError(returned)) => {
+                                                    let retry_value = returned.clone();
+
+                                                    let verdict = tokio::select! {
+                                                        verdict = error_handler(tokio::sync::mpsc::error::SendError(returned)) => Some(verdict),
+                                                        _ = $crate::wait_cancelled(&token), if token.is_some() => None,
+                                                    };
+
+                                                    match verdict {
+                                                        Some($crate::error_handler::ControlFlow::Retry) => retry_value,
+                                                        Some($crate::error_handler::ControlFlow::Drop) => break,
+                                                        Some($crate::error_handler::ControlFlow::Abort) | None => return,
+                                                    }
+                                                }
+                                            };
+                                        }
+                                    }
+                                    None => [<active_ $variant:snake>] = false,
+                                }
+                            }
+                        )+
+                        _ = $crate::wait_cancelled(&token), if token.is_some() => {
+                            break;
+                        }
+                        else => break,
+                    }
+                }
+            });
+
+            (rx, handle)
+        }
+    }};
+}
+
 /// Demultiplexes a stream into several others.
 ///
 /// Accepts a non-empty list of variants in the form `MyEnumPath {VariantName0,
@@ -125,6 +315,15 @@ macro_rules! mux {
 /// from `input_stream` into one of receivers. See also [our default error
 /// handlers].
 ///
+/// This macro forwards as-is to the external [`mux_stream_macros`] crate, so
+/// it predates [`ControlFlow`]-based steering and has neither
+/// [`CancellationToken`] nor [`JoinHandle`] support: its error handler must
+/// still return a bare `BoxFuture<'static, ()>`, not [`ErrorHandler<T>`]'s
+/// `BoxFuture<'static, ControlFlow>`. Wrap one of our handler constructors
+/// with [`error_handler::legacy`] to use it here, e.g.
+/// `error_handler::legacy(error_handler::panicking())`. For `ControlFlow`
+/// steering and cancellation support, use [`demux_with_capacity!`] instead.
+///
 /// # Example
 /// ```
 /// use mux_stream::{demux, error_handler};
@@ -150,7 +349,7 @@ macro_rules! mux {
 /// ]);
 ///
 /// let (mut i32_stream, mut f64_stream, mut str_stream) =
-///     demux!(MyEnum { A, B, C })(stream, error_handler::panicking());
+///     demux!(MyEnum { A, B, C })(stream, error_handler::legacy(error_handler::panicking()));
 ///
 /// assert_eq!(i32_stream.next().await, Some(123));
 /// assert_eq!(i32_stream.next().await, Some(811));
@@ -167,6 +366,12 @@ macro_rules! mux {
 ///
 /// [Tokio task]: tokio::task
 /// [our default error handlers]: crate::error_handler
+/// [`ControlFlow`]: crate::error_handler::ControlFlow
+/// [`ErrorHandler<T>`]: crate::error_handler::ErrorHandler
+/// [`error_handler::legacy`]: crate::error_handler::legacy
+/// [`CancellationToken`]: tokio_util::sync::CancellationToken
+/// [`JoinHandle`]: tokio::task::JoinHandle
+/// [`demux_with_capacity!`]: crate::demux_with_capacity
 #[macro_export]
 macro_rules! demux {
     ($enumeration:path { $($variant:ident),+ $(,)? } $($dot2:tt)?) => {
@@ -174,6 +379,400 @@ macro_rules! demux {
     };
 }
 
+/// Multiplexes several streams into one `Stream`, without spawning a task.
+///
+/// Accepts the same variant-with-stream syntax as [`mux_with_capacity!`]:
+///
+/// ```ignore
+/// mux_stream!(MyEnum { A = stream_a, B = stream_b })
+/// ```
+///
+/// Unlike [`mux!`]/[`mux_with_capacity!`], this does not spawn a [Tokio
+/// task], allocate an intermediate channel, or take an error handler: it
+/// simply maps each input stream into its variant (e.g. `.map(MyEnum::A)`)
+/// and combines all of them with [`futures::stream::select_all`].
+///
+/// `select_all` requires every stream it combines to share one concrete
+/// type, but each `.map(MyEnum::$variant)` here produces a distinct,
+/// unnameable closure type, so each mapped stream is boxed into a
+/// `Pin<Box<dyn Stream<Item = $enum_ty> + Send>>` before being combined.
+///
+/// Because there is no background task or channel, the returned stream
+/// only makes progress while it is being polled, and it inherits whatever
+/// buffering or backpressure its caller applies. This makes it usable on
+/// non-Tokio executors, at the cost of losing the independent forwarding
+/// (and the error handler) that [`mux!`]/[`mux_with_capacity!`] provide.
+///
+/// ```
+/// use mux_stream::mux_stream;
+///
+/// use futures::{stream, StreamExt};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum MyEnum {
+///     A(i32),
+///     B(u8),
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main_() {
+/// let combined = mux_stream!(MyEnum {
+///     A = stream::iter(vec![123, 811]),
+///     B = stream::iter(vec![88]),
+/// });
+///
+/// let mut values: Vec<_> = combined.collect().await;
+/// values.sort_by_key(|update| match update {
+///     MyEnum::A(x) => *x,
+///     MyEnum::B(x) => *x as i32,
+/// });
+/// assert_eq!(values, vec![MyEnum::B(88), MyEnum::A(123), MyEnum::A(811)]);
+/// # }
+/// ```
+///
+/// [`mux!`]: crate::mux
+/// [`mux_with_capacity!`]: crate::mux_with_capacity
+/// [Tokio task]: tokio::task
+#[macro_export]
+macro_rules! mux_stream {
+    ($enum_ty:path { $($variant:ident = $stream:expr),+ $(,)? }) => {{
+        use futures::StreamExt as _;
+
+        futures::stream::select_all(vec![
+            $(
+                std::boxed::Box::pin($stream.map(<$enum_ty>::$variant))
+                    as std::pin::Pin<std::boxed::Box<dyn futures::Stream<Item = $enum_ty> + Send>>
+            ),+
+        ])
+    }};
+}
+
+/// Demultiplexes a stream into several others, backed by bounded channels.
+///
+/// Just like [`demux!`], but every output stream is backed by
+/// [`tokio::sync::mpsc::channel`] rather than the unbounded variant. Because
+/// bounded [`Sender::send`](tokio::sync::mpsc::Sender::send) is `async` and
+/// resolves only once the corresponding output channel has spare capacity, a
+/// slow processor for one variant naturally stalls the demultiplexing of
+/// `input_stream` until it catches up, rather than letting the other
+/// channels grow without bound.
+///
+/// Like [`mux_with_capacity!`], this macro takes every argument in a single
+/// invocation rather than [`demux!`]'s two-stage `demux!(...)(...)` call, for
+/// the same reason (it is implemented directly as `macro_rules!`, with no
+/// external proc-macro crate backing it):
+///
+/// ```ignore
+/// demux_with_capacity!(MyEnum { A, B }, capacity, input_stream, error_handler)
+/// ```
+///
+/// This returns `(tokio::sync::mpsc::Receiver<T[1]>, ...,
+/// tokio::sync::mpsc::Receiver<T[n]>)`, where `T[i]` is the type of the
+/// corresponding provided variant's single unnamed parameter. `..` can be
+/// appended to the variant list for non-exhaustive demultiplexing, just like
+/// [`demux!`].
+///
+/// `error_handler` is invoked when a demultiplexer fails to send an update
+/// from `input_stream` into one of the receivers, and its [`ControlFlow`]
+/// answer decides whether that update is retried, dropped, or the whole task
+/// is aborted. See [our default error handlers].
+///
+/// Each variant's payload type must implement `Clone`: a failed send hands
+/// the update to `error_handler` while keeping a clone around, so it can be
+/// replayed if the answer is [`ControlFlow::Retry`]. This is required even
+/// if `error_handler` never returns `Retry`.
+///
+/// Optionally, a [`tokio_util::sync::CancellationToken`] may be passed as
+/// the last argument, after the error handler. When cancelled, the task
+/// stops pulling from `input_stream`, drops every output sender so all the
+/// output streams terminate cleanly, and returns; pass one in and the
+/// macro also returns the task's [`tokio::task::JoinHandle`] alongside the
+/// output streams, so callers can `.await` it to confirm every in-flight
+/// update was delivered before exiting. A pending retry is also raced
+/// against the token, so cancellation is not stuck behind an error
+/// handler's backoff.
+///
+/// ```
+/// use mux_stream::{demux_with_capacity, error_handler};
+///
+/// use futures::stream;
+///
+/// #[derive(Debug)]
+/// enum MyEnum {
+///     A(i32),
+///     B(f64),
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main_() {
+/// let input = stream::iter(vec![MyEnum::A(123), MyEnum::B(24.241), MyEnum::A(811)]);
+///
+/// let (mut i32_stream, mut f64_stream) =
+///     demux_with_capacity!(MyEnum { A, B }, 16, input, error_handler::panicking());
+///
+/// assert_eq!(i32_stream.recv().await, Some(123));
+/// assert_eq!(i32_stream.recv().await, Some(811));
+/// assert_eq!(i32_stream.recv().await, None);
+///
+/// assert_eq!(f64_stream.recv().await, Some(24.241));
+/// assert_eq!(f64_stream.recv().await, None);
+/// # }
+/// ```
+///
+/// [`demux!`]: crate::demux
+/// [`mux_with_capacity!`]: crate::mux_with_capacity
+/// [our default error handlers]: crate::error_handler
+/// [`ControlFlow`]: crate::error_handler::ControlFlow
+#[macro_export]
+macro_rules! demux_with_capacity {
+    ($enum_ty:path { $($variant:ident),+ $(,)? }, $capacity:expr, $input:expr, $error_handler:expr $(,)?) => {{
+        let (rxs, _handle) = $crate::demux_with_capacity!(
+            @impl $enum_ty { $($variant),+ } [] $capacity, $input, $error_handler, None
+        );
+        rxs
+    }};
+    ($enum_ty:path { $($variant:ident),+ $(,)? } .., $capacity:expr, $input:expr, $error_handler:expr $(,)?) => {{
+        let (rxs, _handle) = $crate::demux_with_capacity!(
+            @impl $enum_ty { $($variant),+ } [_ => {}] $capacity, $input, $error_handler, None
+        );
+        rxs
+    }};
+    ($enum_ty:path { $($variant:ident),+ $(,)? }, $capacity:expr, $input:expr, $error_handler:expr, $token:expr $(,)?) => {
+        $crate::demux_with_capacity!(@impl $enum_ty { $($variant),+ } [] $capacity, $input, $error_handler, Some($token))
+    };
+    ($enum_ty:path { $($variant:ident),+ $(,)? } .., $capacity:expr, $input:expr, $error_handler:expr, $token:expr $(,)?) => {
+        $crate::demux_with_capacity!(@impl $enum_ty { $($variant),+ } [_ => {}] $capacity, $input, $error_handler, Some($token))
+    };
+    (@impl $enum_ty:path { $($variant:ident),+ } [$($catch_all:tt)*] $capacity:expr, $input:expr, $error_handler:expr, $token:expr) => {{
+        use futures::StreamExt as _;
+
+        let capacity: usize = $capacity;
+        let mut input_stream = $input;
+        let token: Option<tokio_util::sync::CancellationToken> = $token;
+
+        paste::paste! {
+            $(let ([<tx_ $variant:snake>], [<rx_ $variant:snake>]) = tokio::sync::mpsc::channel(capacity);)+
+
+            let error_handler = $error_handler;
+
+            let handle = tokio::spawn(async move {
+                loop {
+                    let item = tokio::select! {
+                        item = input_stream.next() => item,
+                        _ = $crate::wait_cancelled(&token), if token.is_some() => break,
+                    };
+
+                    match item {
+                        $(
+                            Some($enum_ty::$variant(payload)) => {
+                                let mut update = payload;
+
+                                loop {
+                                    let sent = tokio::select! {
+                                        result = [<tx_ $variant:snake>].send(update) => Some(result),
+                                        _ = $crate::wait_cancelled(&token), if token.is_some() => None,
+                                    };
+
+                                    let result = match sent {
+                                        Some(result) => result,
+                                        None => return,
+                                    };
+
+                                    update = match result {
+                                        Ok(()) => break,
+                                        Err(tokio::sync::mpsc::error::SendError(returned)) => {
+                                            let retried = returned.clone();
+                                            let reported = <$enum_ty>::$variant(returned);
+
+                                            let verdict = tokio::select! {
+                                                verdict = error_handler(tokio::sync::mpsc::error::SendError(reported)) => Some(verdict),
+                                                _ = $crate::wait_cancelled(&token), if token.is_some() => None,
+                                            };
+
+                                            match verdict {
+                                                Some($crate::error_handler::ControlFlow::Retry) => retried,
+                                                Some($crate::error_handler::ControlFlow::Drop) => break,
+                                                Some($crate::error_handler::ControlFlow::Abort) | None => return,
+                                            }
+                                        }
+                                    };
+                                }
+                            }
+                        )+
+                        None => break,
+                        $($catch_all)*
+                    }
+                }
+            });
+
+            (($([<rx_ $variant:snake>]),+), handle)
+        }
+    }};
+}
+
+/// Demultiplexes a stream into several others, batching each variant's
+/// updates by size and time.
+///
+/// Accepts a non-empty list of variants in the form `MyEnumPath {VariantName0,
+/// ..., VariantNameN}`, just like [`demux_with_capacity!`], taking every
+/// argument in a single invocation:
+///
+/// ```ignore
+/// demux_batched!(MyEnum { A, B }, max_size, timeout, input_stream, error_handler)
+/// ```
+///
+/// Instead of forwarding one update at a time, each output channel buffers
+/// the updates of its variant into a `Vec<T>` and flushes it downstream as
+/// soon as either `max_size` elements have accumulated or `timeout` has
+/// elapsed since the first element was buffered, whichever happens first;
+/// the timer is reset on every flush. An empty buffer never causes a
+/// flush, so an idle variant produces no empty `Vec`s. On termination of
+/// the input stream, any non-empty remainder is flushed before the output
+/// channels are closed.
+///
+/// This returns `(tokio::sync::mpsc::UnboundedReceiver<Vec<T[1]>>, ...,
+/// tokio::sync::mpsc::UnboundedReceiver<Vec<T[n]>>)`, where `T[i]` is the
+/// type of the corresponding provided variant's single unnamed parameter.
+///
+/// Every output channel here is unbounded, so flushing a batch can only
+/// ever fail once its receiver has been dropped, at which point there is
+/// no one left to retry delivery to; `error_handler` is accepted purely
+/// for signature symmetry with [`demux_with_capacity!`] and is not invoked.
+/// See [our default error handlers].
+///
+/// Optionally, a [`tokio_util::sync::CancellationToken`] may be passed as
+/// the last argument, after the error handler, just like
+/// [`demux_with_capacity!`]. When cancelled, the task stops pulling from
+/// `input_stream`, flushes any non-empty remainder exactly as it would on
+/// input-stream termination, and returns; pass one in and the macro also
+/// returns the task's [`tokio::task::JoinHandle`] alongside the output
+/// streams.
+///
+/// ```
+/// use mux_stream::{demux_batched, error_handler};
+///
+/// use std::time::Duration;
+///
+/// use futures::stream;
+///
+/// #[derive(Debug)]
+/// enum MyEnum {
+///     A(i32),
+///     B(u8),
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main_() {
+/// let input = stream::iter(vec![MyEnum::A(1), MyEnum::A(2), MyEnum::A(3), MyEnum::B(9)]);
+///
+/// let (mut a_rx, mut b_rx) = demux_batched!(
+///     MyEnum { A, B },
+///     2,
+///     Duration::from_secs(10),
+///     input,
+///     error_handler::panicking::<MyEnum>(),
+/// );
+///
+/// assert_eq!(a_rx.recv().await, Some(vec![1, 2]));
+/// assert_eq!(a_rx.recv().await, Some(vec![3]));
+/// assert_eq!(a_rx.recv().await, None);
+///
+/// assert_eq!(b_rx.recv().await, Some(vec![9]));
+/// assert_eq!(b_rx.recv().await, None);
+/// # }
+/// ```
+///
+/// [`demux_with_capacity!`]: crate::demux_with_capacity
+/// [our default error handlers]: crate::error_handler
+/// [`ControlFlow`]: crate::error_handler::ControlFlow
+#[macro_export]
+macro_rules! demux_batched {
+    ($enum_ty:path { $($variant:ident),+ $(,)? }, $max_size:expr, $timeout:expr, $input:expr, $error_handler:expr $(,)?) => {{
+        let (rxs, _handle) = $crate::demux_batched!(
+            @impl $enum_ty { $($variant),+ } $max_size, $timeout, $input, $error_handler, None
+        );
+        rxs
+    }};
+    ($enum_ty:path { $($variant:ident),+ $(,)? }, $max_size:expr, $timeout:expr, $input:expr, $error_handler:expr, $token:expr $(,)?) => {
+        $crate::demux_batched!(
+            @impl $enum_ty { $($variant),+ } $max_size, $timeout, $input, $error_handler, Some($token)
+        )
+    };
+    (@impl $enum_ty:path { $($variant:ident),+ } $max_size:expr, $timeout:expr, $input:expr, $error_handler:expr, $token:expr) => {{
+        use futures::StreamExt as _;
+
+        let max_size: usize = $max_size;
+        let timeout: std::time::Duration = $timeout;
+        let mut input_stream = $input;
+        let token: Option<tokio_util::sync::CancellationToken> = $token;
+        let _ = &$error_handler;
+
+        paste::paste! {
+            $(let mut [<buf_ $variant:snake>]: Vec<_> = Vec::new();)+
+            $(let mut [<timer_ $variant:snake>]: Option<std::pin::Pin<Box<tokio::time::Sleep>>> = None;)+
+            $(let ([<tx_ $variant:snake>], [<rx_ $variant:snake>]) = tokio::sync::mpsc::unbounded_channel();)+
+
+            let handle = tokio::spawn(async move {
+                'outer: loop {
+                    tokio::select! {
+                        item = input_stream.next() => {
+                            match item {
+                                $(
+                                    Some($enum_ty::$variant(payload)) => {
+                                        [<buf_ $variant:snake>].push(payload);
+
+                                        if [<buf_ $variant:snake>].len() == 1 {
+                                            [<timer_ $variant:snake>] = Some(Box::pin(tokio::time::sleep(timeout)));
+                                        }
+
+                                        if [<buf_ $variant:snake>].len() >= max_size {
+                                            let batch = std::mem::take(&mut [<buf_ $variant:snake>]);
+                                            let _ = [<tx_ $variant:snake>].send(batch);
+                                            [<timer_ $variant:snake>] = None;
+                                        }
+                                    }
+                                )+
+                                None => {
+                                    $(
+                                        if !([<buf_ $variant:snake>].is_empty()) {
+                                            let batch = std::mem::take(&mut [<buf_ $variant:snake>]);
+                                            let _ = [<tx_ $variant:snake>].send(batch);
+                                        }
+                                    )+
+                                    break 'outer;
+                                }
+                            }
+                        }
+                        $(
+                            _ = async {
+                                match [<timer_ $variant:snake>].as_mut() {
+                                    Some(timer) => timer.await,
+                                    None => std::future::pending().await,
+                                }
+                            }, if [<timer_ $variant:snake>].is_some() => {
+                                let batch = std::mem::take(&mut [<buf_ $variant:snake>]);
+                                let _ = [<tx_ $variant:snake>].send(batch);
+                                [<timer_ $variant:snake>] = None;
+                            }
+                        )+
+                        _ = $crate::wait_cancelled(&token), if token.is_some() => {
+                            $(
+                                if !([<buf_ $variant:snake>].is_empty()) {
+                                    let batch = std::mem::take(&mut [<buf_ $variant:snake>]);
+                                    let _ = [<tx_ $variant:snake>].send(batch);
+                                }
+                            )+
+                            break 'outer;
+                        }
+                    }
+                }
+            });
+
+            (($([<rx_ $variant:snake>]),+), handle)
+        }
+    }};
+}
+
 /// Propagate streams into multiple asynchronous functions.
 ///
 /// This is just a shortcut for passing the results (futures) of the specified